@@ -14,22 +14,50 @@ use rustpython_parser::parser;
 #[cfg(feature = "rustpython-compiler")]
 use rustpython_compiler as compile;
 
-use crate::builtins::{self, PyStrRef, PyTypeRef};
+use crate::builtins::{self, list::PyList, PyStrRef, PyTypeRef};
+use crate::function::OptionalArg;
 use crate::pyobject::{
-    BorrowValue, IdProtocol, ItemProtocol, PyClassImpl, PyContext, PyObjectRef, PyRef, PyResult,
-    PyValue, StaticType, TryFromObject, TypeProtocol,
+    BorrowValue, IdProtocol, ItemProtocol, PyClassImpl, PyObjectRef, PyRef, PyResult, PyValue,
+    StaticType, TryFromObject, TypeProtocol,
 };
 use crate::vm::VirtualMachine;
 
 #[rustfmt::skip]
 mod gen;
-
+mod dump;
+mod fold;
+mod optimize;
+mod unparser;
+
+pub(crate) use optimize::fold_constants;
+
+/// Names set as `lineno`/`col_offset` (and friends) on every node instance by
+/// [`node_add_location`], and exposed as `AstNode._attributes` (inherited by
+/// every generated node class) below.
+const NODE_ATTRIBUTE_NAMES: &[&str] = &["lineno", "col_offset", "end_lineno", "end_col_offset"];
+
+/// Record a node's `lineno`/`col_offset`/`end_lineno`/`end_col_offset`, the
+/// way every generated `ast_to_object` in `gen::extend_module_nodes` already
+/// calls this.
+///
+/// KNOWN LIMITATION, not yet fixed: `gen::extend_module_nodes` only ever
+/// passes this function a single `ast::Location`, because the ASDL codegen
+/// that produces it (out of scope for this series — that generator lives
+/// outside this module and nothing here touches it) doesn't track each
+/// node's *end* position at all. So until that codegen is updated to thread
+/// an end location through, `end_lineno`/`end_col_offset` are set to `None`
+/// on every node, unconditionally. This is flagged here rather than hidden
+/// behind a signature that looks like it already does the job.
 fn node_add_location(node: &AstNodeRef, location: ast::Location, vm: &VirtualMachine) {
     let dict = node.as_object().dict().unwrap();
     dict.set_item("lineno", vm.ctx.new_int(location.row()), vm)
         .unwrap();
     dict.set_item("col_offset", vm.ctx.new_int(location.column()), vm)
         .unwrap();
+    // See the limitation noted above: always `None` until codegen tracks end
+    // positions.
+    dict.set_item("end_lineno", vm.ctx.none(), vm).unwrap();
+    dict.set_item("end_col_offset", vm.ctx.none(), vm).unwrap();
 }
 
 fn get_node_field(vm: &VirtualMachine, obj: &PyObjectRef, field: &str, _typ: &str) -> PyResult {
@@ -37,13 +65,98 @@ fn get_node_field(vm: &VirtualMachine, obj: &PyObjectRef, field: &str, _typ: &st
     vm.get_attribute(obj.clone(), field)
 }
 
+/// The `(name, value)` pairs of a node's own fields, in declaration order.
+///
+/// `_fields` can't be a static per-class tuple written by the generated
+/// `gen::extend_module_nodes`, because that codegen lives outside this
+/// module and isn't touched here. Instead this reads it straight off the
+/// instance: every generated `ast_to_object` sets exactly the node's fields
+/// (in ASDL order) on the instance dict before [`node_add_location`] adds
+/// the location attributes, so "the dict, minus the known attribute names"
+/// *is* `_fields` — no codegen changes required.
+pub(crate) fn node_field_values(
+    vm: &VirtualMachine,
+    node: &PyObjectRef,
+) -> PyResult<Vec<(PyStrRef, PyObjectRef)>> {
+    let dict = node
+        .dict()
+        .ok_or_else(|| vm.new_type_error("not an AST node".to_owned()))?;
+    dict.into_iter()
+        .filter(|(key, _)| {
+            PyStrRef::try_from_object(vm, key.clone())
+                .map(|name| !NODE_ATTRIBUTE_NAMES.contains(&name.borrow_value()))
+                .unwrap_or(true)
+        })
+        .map(|(key, value)| Ok((PyStrRef::try_from_object(vm, key)?, value)))
+        .collect()
+}
+
 #[pyclass(module = "_ast", name = "AST")]
 #[derive(Debug)]
 pub(crate) struct AstNode;
 type AstNodeRef = PyRef<AstNode>;
 
 #[pyimpl(flags(HAS_DICT))]
-impl AstNode {}
+impl AstNode {
+    /// The field names CPython exposes as `_fields`, computed per-instance
+    /// (see [`node_field_values`]).
+    ///
+    /// KNOWN LIMITATION: CPython's `_fields` is a class-level tuple, so
+    /// `SomeNodeType._fields` (no instance in hand) works there. This is a
+    /// `#[pyproperty]`, which is an instance-bound descriptor — accessing it
+    /// on the class itself (rather than on a node instance) does not return
+    /// a tuple the way CPython does. A real class-level `_fields` would need
+    /// the ASDL codegen (out of scope here, see [`node_add_location`]) to
+    /// emit it per generated node type. Only instance-level access
+    /// (`some_node._fields`) is supported by this implementation.
+    #[pyproperty(name = "_fields")]
+    fn fields(zelf: PyRef<Self>, vm: &VirtualMachine) -> PyResult<PyObjectRef> {
+        let names = node_field_values(vm, zelf.as_object())?
+            .into_iter()
+            .map(|(name, _)| name.into_object())
+            .collect();
+        Ok(vm.ctx.new_tuple(names))
+    }
+
+    /// The attribute names CPython exposes as `_attributes`. Unlike
+    /// `_fields` these are the same for every node kind, so a single class
+    /// attribute on this common base (inherited by every generated node
+    /// class) is enough.
+    #[pyattr(name = "_attributes")]
+    fn attributes(vm: &VirtualMachine) -> PyObjectRef {
+        vm.ctx.new_tuple(
+            NODE_ATTRIBUTE_NAMES
+                .iter()
+                .map(|&name| vm.ctx.new_str(name))
+                .collect(),
+        )
+    }
+
+    /// Return, as a list, the direct `AstNode` children of `self` that
+    /// CPython's `ast.iter_child_nodes` yields, by walking the instance's
+    /// fields.
+    #[pymethod]
+    fn iter_child_nodes(zelf: PyRef<Self>, vm: &VirtualMachine) -> PyResult<Vec<PyObjectRef>> {
+        let mut children = Vec::new();
+        for (_, value) in node_field_values(vm, zelf.as_object())? {
+            collect_child_nodes(vm, &value, &mut children);
+        }
+        Ok(children)
+    }
+}
+
+/// Recursively collect `AstNode` instances out of a field value, descending
+/// into plain lists the way CPython's `ast` module does (e.g. a `body`
+/// field holding a list of statement nodes).
+fn collect_child_nodes(vm: &VirtualMachine, value: &PyObjectRef, out: &mut Vec<PyObjectRef>) {
+    if let Ok(list) = value.clone().downcast::<PyList>() {
+        for item in list.borrow_value().iter() {
+            collect_child_nodes(vm, item, out);
+        }
+    } else if vm.isinstance(value, AstNode::static_type()).unwrap_or(false) {
+        out.push(value.clone());
+    }
+}
 
 const MODULE_NAME: &str = "_ast";
 pub const PY_COMPILE_FLAG_AST_ONLY: i32 = 0x0400;
@@ -211,13 +324,33 @@ pub(crate) fn compile(
     _mode: compile::Mode,
 ) -> PyResult {
     let opts = vm.compile_opts();
-    let ast = Node::ast_from_object(vm, object)?;
+    let mut ast = Node::ast_from_object(vm, object)?;
+    fold_constants(&mut ast, vm);
     let code = rustpython_compiler_core::compile::compile_top(&ast, filename.to_owned(), opts)
         // TODO: use vm.new_syntax_error()
         .map_err(|err| vm.new_value_error(err.to_string()))?;
     Ok(vm.new_code_object(code).into_object())
 }
 
+fn unparse(object: PyObjectRef, vm: &VirtualMachine) -> PyResult<String> {
+    let ast: ast::Mod = Node::ast_from_object(vm, object)?;
+    unparser::unparse(&ast, vm)
+}
+
+fn dump(
+    node: PyObjectRef,
+    annotate_fields: OptionalArg<bool>,
+    include_attributes: OptionalArg<bool>,
+    vm: &VirtualMachine,
+) -> PyResult<String> {
+    dump::dump(
+        vm,
+        &node,
+        annotate_fields.unwrap_or(true),
+        include_attributes.unwrap_or(false),
+    )
+}
+
 pub fn make_module(vm: &VirtualMachine) -> PyObjectRef {
     let ctx = &vm.ctx;
 
@@ -226,6 +359,8 @@ pub fn make_module(vm: &VirtualMachine) -> PyObjectRef {
         // TODO: There's got to be a better way!
         "AST" => ast_base,
         "PyCF_ONLY_AST" => ctx.new_int(PY_COMPILE_FLAG_AST_ONLY),
+        "unparse" => named_function!(ctx, ast, unparse),
+        "dump" => named_function!(ctx, ast, dump),
     });
     gen::extend_module_nodes(vm, &module);
     module
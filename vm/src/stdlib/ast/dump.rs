@@ -0,0 +1,135 @@
+//! `ast.dump()`: render an AST object to a deterministic, human-readable
+//! string, e.g. `BinOp(left=Constant(value=1), op=Add(), right=Constant(value=2))`.
+//!
+//! This walks the *generic* node shape via [`super::node_field_values`]
+//! rather than recovering the typed `rustpython_ast` tree, so it works
+//! uniformly over nodes produced by the parser as well as ones constructed
+//! or mutated from Python.
+
+use crate::builtins::list::PyList;
+use crate::pyobject::{BorrowValue, PyObjectRef, PyResult, StaticType, TypeProtocol};
+use crate::vm::VirtualMachine;
+
+use super::{node_field_values, AstNode, NODE_ATTRIBUTE_NAMES};
+
+pub(super) fn dump(
+    vm: &VirtualMachine,
+    node: &PyObjectRef,
+    annotate_fields: bool,
+    include_attributes: bool,
+) -> PyResult<String> {
+    let mut out = String::new();
+    dump_value(vm, node, annotate_fields, include_attributes, &mut out)?;
+    Ok(out)
+}
+
+fn dump_value(
+    vm: &VirtualMachine,
+    value: &PyObjectRef,
+    annotate_fields: bool,
+    include_attributes: bool,
+    out: &mut String,
+) -> PyResult<()> {
+    if vm.isinstance(value, AstNode::static_type())? {
+        dump_node(vm, value, annotate_fields, include_attributes, out)
+    } else if let Ok(list) = value.clone().downcast::<PyList>() {
+        out.push('[');
+        for (i, item) in list.borrow_value().iter().enumerate() {
+            if i > 0 {
+                out.push_str(", ");
+            }
+            dump_value(vm, item, annotate_fields, include_attributes, out)?;
+        }
+        out.push(']');
+        Ok(())
+    } else {
+        let repr = vm.to_repr(value)?;
+        out.push_str(repr.borrow_value());
+        Ok(())
+    }
+}
+
+fn dump_node(
+    vm: &VirtualMachine,
+    node: &PyObjectRef,
+    annotate_fields: bool,
+    include_attributes: bool,
+    out: &mut String,
+) -> PyResult<()> {
+    out.push_str(&node.class().name);
+    out.push('(');
+
+    let mut parts = Vec::new();
+    for (name, value) in node_field_values(vm, node)? {
+        // `node_field_values` reads straight off the instance dict, so an
+        // unset *optional* field shows up as a present `None` rather than
+        // being absent the way a truly-missing attribute would be. Skip
+        // those to match CPython's "omitted optional fields are skipped",
+        // except `Constant.value`, whose own legitimate value (the `None`
+        // literal) is also `None` and must still be shown.
+        let is_constant_value =
+            node.class().name == "Constant" && name.borrow_value() == "value";
+        if vm.is_none(&value) && !is_constant_value {
+            continue;
+        }
+        let mut part = String::new();
+        if annotate_fields {
+            part.push_str(name.borrow_value());
+            part.push('=');
+        }
+        dump_value(vm, &value, annotate_fields, include_attributes, &mut part)?;
+        parts.push(part);
+    }
+
+    if include_attributes {
+        for name in NODE_ATTRIBUTE_NAMES {
+            if let Ok(value) = vm.get_attribute(node.clone(), *name) {
+                let mut part = String::new();
+                if annotate_fields {
+                    part.push_str(name);
+                    part.push('=');
+                }
+                dump_value(vm, &value, annotate_fields, include_attributes, &mut part)?;
+                parts.push(part);
+            }
+        }
+    }
+
+    out.push_str(&parts.join(", "));
+    out.push(')');
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stdlib::ast::Node as _;
+    use crate::Interpreter;
+
+    fn dump_source(source: &str) -> String {
+        Interpreter::without_stdlib(Default::default()).enter(|vm| {
+            let module =
+                rustpython_parser::parser::parse(source, rustpython_parser::parser::Mode::Eval)
+                    .unwrap();
+            let object = module.ast_to_object(vm);
+            dump(vm, &object, true, false).unwrap()
+        })
+    }
+
+    /// A present-but-optional field whose value is `None` (e.g.
+    /// `Constant.kind`) must be omitted, matching CPython.
+    #[test]
+    fn omits_none_optional_fields() {
+        assert_eq!(dump_source("1"), "Expression(body=Constant(value=1))");
+    }
+
+    /// `Constant.value` is itself legitimately `None` for the `None`
+    /// literal, and must still be shown even though it's `None`.
+    #[test]
+    fn keeps_constant_value_none() {
+        assert_eq!(
+            dump_source("None"),
+            "Expression(body=Constant(value=None))"
+        );
+    }
+}
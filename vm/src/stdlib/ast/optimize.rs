@@ -0,0 +1,315 @@
+//! A purely syntactic constant-folding pass over the AST, expressed as a
+//! [`super::fold::Fold`] implementation.
+//!
+//! This runs bottom-up (by relying on `Fold`'s default recursion) and
+//! replaces subtrees that consist entirely of literals with a single
+//! `ast::Constant`, e.g. `1 + 2` becomes `3` and `"a" + "b"` becomes `"ab"`.
+//! It never touches names, calls, or attribute access, and it leaves a node
+//! untouched whenever folding it would raise (division by zero, a shift by
+//! a negative amount, etc.) so that the runtime error is still produced at
+//! the original expression's location.
+
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{Signed, ToPrimitive, Zero};
+
+use rustpython_ast as ast;
+
+use super::fold::{self, Fold};
+use crate::pyobject::PyResult;
+use crate::vm::VirtualMachine;
+
+/// Fold constant subexpressions in `module` in place.
+pub(crate) fn fold_constants(module: &mut ast::Mod, vm: &VirtualMachine) {
+    let placeholder = ast::Mod::Module {
+        body: Vec::new(),
+        type_ignores: Vec::new(),
+    };
+    let taken = std::mem::replace(module, placeholder);
+    // `ConstantFolder` never actually fails, so this can't panic.
+    *module = ConstantFolder
+        .fold_mod(taken, vm)
+        .expect("constant folding is infallible");
+}
+
+struct ConstantFolder;
+
+impl Fold for ConstantFolder {
+    fn fold_expr_kind(
+        &mut self,
+        node: ast::ExprKind,
+        vm: &VirtualMachine,
+    ) -> PyResult<ast::ExprKind> {
+        use ast::ExprKind::*;
+        // recurse into children first via the default traversal, then try
+        // to collapse the (now possibly-constant) result.
+        let node = fold::walk_expr_kind(self, node, vm)?;
+        Ok(match node {
+            BinOp { left, op, right } => match (&left.node, &right.node) {
+                (Constant { value: l, .. }, Constant { value: r, .. }) => {
+                    match fold_binop(l, op, r) {
+                        Some(value) => Constant { value, kind: None },
+                        None => BinOp { left, op, right },
+                    }
+                }
+                _ => BinOp { left, op, right },
+            },
+            UnaryOp { op, operand } => match &operand.node {
+                Constant { value, .. } => match fold_unaryop(op, value) {
+                    Some(value) => Constant { value, kind: None },
+                    None => UnaryOp { op, operand },
+                },
+                _ => UnaryOp { op, operand },
+            },
+            Tuple { elts, ctx } if matches!(ctx, ast::ExprContext::Load) => {
+                if elts.iter().all(|e| matches!(e.node, Constant { .. })) {
+                    let values = elts
+                        .iter()
+                        .map(|e| match &e.node {
+                            Constant { value, .. } => value.clone(),
+                            _ => unreachable!(),
+                        })
+                        .collect();
+                    Constant {
+                        value: ast::Constant::Tuple(values),
+                        kind: None,
+                    }
+                } else {
+                    Tuple { elts, ctx }
+                }
+            }
+            other => other,
+        })
+    }
+}
+
+/// A folded numeric constant, before it's wrapped back up as `ast::Constant`.
+/// `bool` is treated as `int` for arithmetic, matching the runtime.
+#[derive(Clone)]
+enum Num {
+    Int(BigInt),
+    Float(f64),
+}
+
+fn as_num(c: &ast::Constant) -> Option<Num> {
+    match c {
+        ast::Constant::Int(i) => Some(Num::Int(i.clone())),
+        ast::Constant::Bool(b) => Some(Num::Int(BigInt::from(*b as u8))),
+        ast::Constant::Float(f) => Some(Num::Float(*f)),
+        _ => None,
+    }
+}
+
+/// `None` for an int too large for a float, which raises `OverflowError` at
+/// runtime.
+fn to_f64(n: &Num) -> Option<f64> {
+    match n {
+        Num::Int(i) => i.to_f64().filter(|f| f.is_finite()),
+        Num::Float(f) => Some(*f),
+    }
+}
+
+/// Evaluate a binary operator on two constants, returning `None` (leaving
+/// the original node alone) whenever the runtime would raise.
+fn fold_binop(
+    left: &ast::Constant,
+    op: ast::Operator,
+    right: &ast::Constant,
+) -> Option<ast::Constant> {
+    use ast::Constant::*;
+    use ast::Operator::*;
+
+    // string/bytes concatenation
+    match (left, op, right) {
+        (Str(l), Add, Str(r)) => return Some(Str(format!("{}{}", l, r))),
+        (Bytes(l), Add, Bytes(r)) => {
+            let mut buf = l.clone();
+            buf.extend_from_slice(r);
+            return Some(Bytes(buf));
+        }
+        // bitwise ops on two bools give a bool at runtime, not an int
+        (Bool(l), BitOr, Bool(r)) => return Some(Bool(l | r)),
+        (Bool(l), BitXor, Bool(r)) => return Some(Bool(l ^ r)),
+        (Bool(l), BitAnd, Bool(r)) => return Some(Bool(l & r)),
+        _ => {}
+    }
+
+    let (l, r) = (as_num(left)?, as_num(right)?);
+
+    // bitwise/shift ops are only defined for integers; a float operand
+    // should hit the runtime's usual `TypeError`, so leave those alone.
+    if matches!(op, BitOr | BitXor | BitAnd | LShift | RShift) {
+        let (l, r) = match (&l, &r) {
+            (Num::Int(l), Num::Int(r)) => (l, r),
+            _ => return None,
+        };
+        let result = match op {
+            BitOr => l | r,
+            BitXor => l ^ r,
+            BitAnd => l & r,
+            LShift if !r.is_negative() => l << r.to_u32()?,
+            RShift if !r.is_negative() => l >> r.to_u32()?,
+            _ => return None,
+        };
+        return Some(ast::Constant::Int(result));
+    }
+
+    match op {
+        Add | Sub | Mult => Some(match (l, r) {
+            (Num::Int(l), Num::Int(r)) => Int(match op {
+                Add => l + r,
+                Sub => l - r,
+                Mult => l * r,
+                _ => unreachable!(),
+            }),
+            (l, r) => {
+                let (l, r) = (to_f64(&l)?, to_f64(&r)?);
+                Float(match op {
+                    Add => l + r,
+                    Sub => l - r,
+                    Mult => l * r,
+                    _ => unreachable!(),
+                })
+            }
+        }),
+        Div => {
+            let (l, r) = (to_f64(&l)?, to_f64(&r)?);
+            if r == 0.0 {
+                None
+            } else {
+                Some(Float(l / r))
+            }
+        }
+        FloorDiv => match (&l, &r) {
+            (Num::Int(l), Num::Int(r)) if !r.is_zero() => Some(Int(l.div_floor(r))),
+            _ => {
+                let (l, r) = (to_f64(&l)?, to_f64(&r)?);
+                if r == 0.0 {
+                    None
+                } else {
+                    // Python derives float `//` from `fmod` rather than
+                    // `floor(l / r)`, which differs for e.g. `1 // 0.1`.
+                    let m = l % r;
+                    let div = (l - m) / r;
+                    let div = if m != 0.0 && (m < 0.0) != (r < 0.0) { div - 1.0 } else { div };
+                    let floor = div.floor();
+                    Some(Float(if div - floor > 0.5 { floor + 1.0 } else { floor }))
+                }
+            }
+        },
+        Mod => match (&l, &r) {
+            (Num::Int(l), Num::Int(r)) if !r.is_zero() => Some(Int(l.mod_floor(r))),
+            _ => {
+                let (l, r) = (to_f64(&l)?, to_f64(&r)?);
+                // Python's float `%` takes the sign of the divisor, same as
+                // floor division's remainder; `f64::rem_euclid` doesn't.
+                if r == 0.0 {
+                    None
+                } else {
+                    let m = l % r;
+                    Some(Float(if m == 0.0 {
+                        0.0f64.copysign(r)
+                    } else if (m < 0.0) != (r < 0.0) {
+                        m + r
+                    } else {
+                        m
+                    }))
+                }
+            }
+        },
+        Pow => match (&l, &r) {
+            (Num::Int(base), Num::Int(exp)) if !exp.is_negative() => {
+                exp.to_u32().map(|exp| Int(base.pow(exp)))
+            }
+            _ => {
+                let (l, r) = (to_f64(&l)?, to_f64(&r)?);
+                // a zero base with a negative exponent raises
+                // `ZeroDivisionError` at runtime (int or float); `0.0f64.powf`
+                // would instead silently return `inf`, so leave it unfolded.
+                // Likewise an overflowing result (`OverflowError` at runtime)
+                // and a NaN one (a negative base with a fractional exponent
+                // gives a complex number at runtime) are left unfolded.
+                if (l == 0.0 && r < 0.0) || !l.powf(r).is_finite() {
+                    None
+                } else {
+                    Some(Float(l.powf(r)))
+                }
+            }
+        },
+        _ => None,
+    }
+}
+
+fn fold_unaryop(op: ast::Unaryop, value: &ast::Constant) -> Option<ast::Constant> {
+    use ast::Constant::*;
+    use ast::Unaryop::*;
+    match (op, value) {
+        (Not, Bool(b)) => Some(Bool(!b)),
+        (Not, Int(i)) => Some(Bool(i.is_zero())),
+        (Not, Float(f)) => Some(Bool(*f == 0.0)),
+        (UAdd, Int(i)) => Some(Int(i.clone())),
+        (USub, Int(i)) => Some(Int(-i)),
+        (Invert, Int(i)) => Some(Int(!i)),
+        (UAdd, Float(f)) => Some(Float(*f)),
+        (USub, Float(f)) => Some(Float(-f)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Interpreter;
+
+    fn fold_expr_source(source: &str) -> ast::ExprKind {
+        Interpreter::without_stdlib(Default::default()).enter(|vm| {
+            let mut module =
+                rustpython_parser::parser::parse(source, rustpython_parser::parser::Mode::Eval)
+                    .unwrap();
+            fold_constants(&mut module, vm);
+            match module {
+                ast::Mod::Expression { body } => body.node,
+                _ => unreachable!(),
+            }
+        })
+    }
+
+    /// `0 ** -1` (and the float/mixed equivalents) must raise
+    /// `ZeroDivisionError` at runtime, so folding must leave the `BinOp`
+    /// alone rather than producing the `inf` that `f64::powf` would give.
+    #[test]
+    fn zero_base_negative_pow_is_not_folded() {
+        for source in ["0 ** -1", "0.0 ** -1", "0 ** -1.0"] {
+            assert!(
+                matches!(fold_expr_source(source), ast::ExprKind::BinOp { .. }),
+                "{} should not have folded",
+                source,
+            );
+        }
+    }
+
+    #[test]
+    fn not_folds_numeric_constants() {
+        assert!(matches!(
+            fold_expr_source("not 0"),
+            ast::ExprKind::Constant {
+                value: ast::Constant::Bool(true),
+                ..
+            }
+        ));
+        assert!(matches!(
+            fold_expr_source("not 1"),
+            ast::ExprKind::Constant {
+                value: ast::Constant::Bool(false),
+                ..
+            }
+        ));
+        assert!(matches!(
+            fold_expr_source("not 0.0"),
+            ast::ExprKind::Constant {
+                value: ast::Constant::Bool(true),
+                ..
+            }
+        ));
+    }
+}
@@ -0,0 +1,869 @@
+//! Reconstruct Python source text from an `ast::Mod` tree.
+//!
+//! This is a best-effort unparser: the output is not guaranteed to be
+//! byte-identical to what CPython's `ast.unparse` would produce, but it is
+//! guaranteed to re-parse to an equivalent tree. Parentheses are only
+//! inserted around a child expression when its operator precedence is lower
+//! than its parent's; tuples are always bracketed. Node kinds that aren't reconstructed yet (e.g. `With`,
+//! `Try`, `Match`) raise rather than silently emitting different-semantics
+//! source.
+
+use std::fmt::Write as _;
+
+use rustpython_ast as ast;
+
+use crate::pyobject::PyResult;
+use crate::vm::VirtualMachine;
+
+/// Operator precedence, lowest to highest. Used to decide whether a child
+/// expression needs to be wrapped in parentheses.
+#[derive(Copy, Clone, PartialEq, PartialOrd)]
+enum Precedence {
+    Tuple,
+    Lambda,
+    Ternary,
+    Or,
+    And,
+    Not,
+    Compare,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Shift,
+    Additive,
+    Multiplicative,
+    Unary,
+    Power,
+    Await,
+    Atom,
+}
+
+pub(super) fn unparse(module: &ast::Mod, vm: &VirtualMachine) -> PyResult<String> {
+    let mut unparser = Unparser::new(vm);
+    match module {
+        ast::Mod::Module { body, .. } | ast::Mod::Interactive { body } => {
+            unparser.body(body)?;
+        }
+        ast::Mod::Expression { body } => {
+            unparser.expr(body, Precedence::Tuple)?;
+            unparser.newline();
+        }
+        ast::Mod::FunctionType { argtypes, returns } => {
+            unparser.push('(');
+            unparser.exprs(argtypes)?;
+            unparser.push_str(") -> ");
+            unparser.expr(returns, Precedence::Tuple)?;
+        }
+    }
+    Ok(unparser.buffer)
+}
+
+struct Unparser<'vm> {
+    buffer: String,
+    indent: usize,
+    vm: &'vm VirtualMachine,
+}
+
+impl<'vm> Unparser<'vm> {
+    fn new(vm: &'vm VirtualMachine) -> Self {
+        Unparser {
+            buffer: String::new(),
+            indent: 0,
+            vm,
+        }
+    }
+
+    fn push(&mut self, c: char) {
+        self.buffer.push(c);
+    }
+
+    fn push_str(&mut self, s: &str) {
+        self.buffer.push_str(s);
+    }
+
+    fn newline(&mut self) {
+        self.buffer.push('\n');
+        for _ in 0..self.indent {
+            self.buffer.push_str("    ");
+        }
+    }
+
+    fn block<F: FnOnce(&mut Self) -> PyResult<()>>(&mut self, body: F) -> PyResult<()> {
+        self.indent += 1;
+        let result = body(self);
+        self.indent -= 1;
+        result
+    }
+
+    fn body(&mut self, stmts: &[ast::Stmt]) -> PyResult<()> {
+        for stmt in stmts {
+            self.newline();
+            self.stmt(stmt)?;
+        }
+        Ok(())
+    }
+
+    fn suite(&mut self, stmts: &[ast::Stmt]) -> PyResult<()> {
+        self.push(':');
+        self.block(|u| u.body(stmts))
+    }
+
+    fn stmt(&mut self, stmt: &ast::Stmt) -> PyResult<()> {
+        use ast::StmtKind::*;
+        match &stmt.node {
+            FunctionDef {
+                name,
+                args,
+                body,
+                decorator_list,
+                returns,
+                ..
+            }
+            | AsyncFunctionDef {
+                name,
+                args,
+                body,
+                decorator_list,
+                returns,
+                ..
+            } => {
+                for dec in decorator_list {
+                    self.push('@');
+                    self.expr(dec, Precedence::Tuple)?;
+                    self.newline();
+                }
+                if matches!(&stmt.node, AsyncFunctionDef { .. }) {
+                    self.push_str("async ");
+                }
+                write!(self.buffer, "def {}(", name).unwrap();
+                self.arguments(args)?;
+                self.push(')');
+                if let Some(returns) = returns {
+                    self.push_str(" -> ");
+                    self.expr(returns, Precedence::Tuple)?;
+                }
+                self.suite(body)?;
+            }
+            ClassDef {
+                name,
+                bases,
+                keywords,
+                body,
+                decorator_list,
+            } => {
+                for dec in decorator_list {
+                    self.push('@');
+                    self.expr(dec, Precedence::Tuple)?;
+                    self.newline();
+                }
+                write!(self.buffer, "class {}", name).unwrap();
+                if !bases.is_empty() || !keywords.is_empty() {
+                    self.push('(');
+                    self.call_args(bases, keywords)?;
+                    self.push(')');
+                }
+                self.suite(body)?;
+            }
+            Return { value } => {
+                self.push_str("return");
+                if let Some(value) = value {
+                    self.push(' ');
+                    self.expr(value, Precedence::Tuple)?;
+                }
+            }
+            Delete { targets } => {
+                self.push_str("del ");
+                self.exprs(targets)?;
+            }
+            Assign { targets, value, .. } => {
+                for target in targets {
+                    self.expr(target, Precedence::Tuple)?;
+                    self.push_str(" = ");
+                }
+                self.expr(value, Precedence::Tuple)?;
+            }
+            AugAssign { target, op, value } => {
+                self.expr(target, Precedence::Tuple)?;
+                write!(self.buffer, " {}= ", operator_str(op)).unwrap();
+                self.expr(value, Precedence::Tuple)?;
+            }
+            AnnAssign {
+                target,
+                annotation,
+                value,
+                ..
+            } => {
+                self.expr(target, Precedence::Tuple)?;
+                self.push_str(": ");
+                self.expr(annotation, Precedence::Tuple)?;
+                if let Some(value) = value {
+                    self.push_str(" = ");
+                    self.expr(value, Precedence::Tuple)?;
+                }
+            }
+            For {
+                target,
+                iter,
+                body,
+                orelse,
+                ..
+            }
+            | AsyncFor {
+                target,
+                iter,
+                body,
+                orelse,
+                ..
+            } => {
+                if matches!(&stmt.node, AsyncFor { .. }) {
+                    self.push_str("async ");
+                }
+                self.push_str("for ");
+                self.expr(target, Precedence::Tuple)?;
+                self.push_str(" in ");
+                self.expr(iter, Precedence::Tuple)?;
+                self.suite(body)?;
+                if !orelse.is_empty() {
+                    self.newline();
+                    self.push_str("else");
+                    self.suite(orelse)?;
+                }
+            }
+            While { test, body, orelse } => {
+                self.push_str("while ");
+                self.expr(test, Precedence::Tuple)?;
+                self.suite(body)?;
+                if !orelse.is_empty() {
+                    self.newline();
+                    self.push_str("else");
+                    self.suite(orelse)?;
+                }
+            }
+            If { test, body, orelse } => {
+                self.push_str("if ");
+                self.expr(test, Precedence::Tuple)?;
+                self.suite(body)?;
+                if !orelse.is_empty() {
+                    self.newline();
+                    self.push_str("else");
+                    self.suite(orelse)?;
+                }
+            }
+            Expr { value } => self.expr(value, Precedence::Tuple)?,
+            Pass => self.push_str("pass"),
+            Break => self.push_str("break"),
+            Continue => self.push_str("continue"),
+            Global { names } => {
+                self.push_str("global ");
+                self.push_str(&names.join(", "));
+            }
+            Nonlocal { names } => {
+                self.push_str("nonlocal ");
+                self.push_str(&names.join(", "));
+            }
+            Import { names } | ImportFrom { names, .. } => {
+                if let ImportFrom { module, level, .. } = &stmt.node {
+                    self.push_str("from ");
+                    self.push_str(&".".repeat(level.unwrap_or(0)));
+                    if let Some(module) = module {
+                        self.push_str(module);
+                    }
+                    self.push_str(" import ");
+                } else {
+                    self.push_str("import ");
+                }
+                let aliases: Vec<String> = names
+                    .iter()
+                    .map(|alias| match &alias.node.asname {
+                        Some(asname) => format!("{} as {}", alias.node.name, asname),
+                        None => alias.node.name.clone(),
+                    })
+                    .collect();
+                self.push_str(&aliases.join(", "));
+            }
+            Raise { exc, cause } => {
+                self.push_str("raise");
+                if let Some(exc) = exc {
+                    self.push(' ');
+                    self.expr(exc, Precedence::Tuple)?;
+                }
+                if let Some(cause) = cause {
+                    self.push_str(" from ");
+                    self.expr(cause, Precedence::Tuple)?;
+                }
+            }
+            other => {
+                // Remaining statement kinds (With, Try, Assert, Match, ...)
+                // aren't reconstructed yet. Fabricating a `pass` here would
+                // silently change the program's semantics, so raise instead.
+                return Err(self
+                    .vm
+                    .new_value_error(format!("don't know how to unparse {:?}", other)));
+            }
+        }
+        Ok(())
+    }
+
+    fn arguments(&mut self, args: &ast::Arguments) -> PyResult<()> {
+        // Only plain, unannotated positional parameters are reconstructed;
+        // dropping anything else would change the signature, so raise.
+        if !args.posonlyargs.is_empty()
+            || args.vararg.is_some()
+            || !args.kwonlyargs.is_empty()
+            || args.kwarg.is_some()
+            || !args.defaults.is_empty()
+            || args.args.iter().any(|arg| arg.node.annotation.is_some())
+        {
+            return Err(self
+                .vm
+                .new_value_error("don't know how to unparse these arguments".to_owned()));
+        }
+        let mut first = true;
+        for arg in &args.args {
+            if !first {
+                self.push_str(", ");
+            }
+            first = false;
+            self.push_str(&arg.node.arg);
+        }
+        Ok(())
+    }
+
+    fn call_args(&mut self, args: &[ast::Expr], keywords: &[ast::Keyword]) -> PyResult<()> {
+        let mut first = true;
+        for arg in args {
+            if !first {
+                self.push_str(", ");
+            }
+            first = false;
+            self.expr(arg, Precedence::Tuple)?;
+        }
+        for kw in keywords {
+            if !first {
+                self.push_str(", ");
+            }
+            first = false;
+            match &kw.node.arg {
+                Some(name) => {
+                    write!(self.buffer, "{}=", name).unwrap();
+                    self.expr(&kw.node.value, Precedence::Tuple)?;
+                }
+                None => {
+                    self.push_str("**");
+                    self.expr(&kw.node.value, Precedence::Tuple)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn exprs(&mut self, exprs: &[ast::Expr]) -> PyResult<()> {
+        let mut first = true;
+        for expr in exprs {
+            if !first {
+                self.push_str(", ");
+            }
+            first = false;
+            self.expr(expr, Precedence::Tuple)?;
+        }
+        Ok(())
+    }
+
+    fn expr(&mut self, expr: &ast::Expr, parent_prec: Precedence) -> PyResult<()> {
+        let prec = precedence(&expr.node);
+        let needs_parens = prec < parent_prec;
+        if needs_parens {
+            self.push('(');
+        }
+        self.expr_inner(expr, prec)?;
+        if needs_parens {
+            self.push(')');
+        }
+        Ok(())
+    }
+
+    fn expr_inner(&mut self, expr: &ast::Expr, prec: Precedence) -> PyResult<()> {
+        use ast::ExprKind::*;
+        match &expr.node {
+            BoolOp { op, values } => {
+                let op_str = match op {
+                    ast::Boolop::And => " and ",
+                    ast::Boolop::Or => " or ",
+                };
+                // As with `Compare` below: an explicitly-nested same-operator
+                // `BoolOp` (`(a or b) or c`, written with parens in the
+                // source) must keep its parens, since CPython always
+                // flattens a bare chain (`a or b or c`) into one `BoolOp`'s
+                // `values` rather than nesting. Strictly-higher precedence
+                // on every operand forces parens exactly when a child is
+                // itself a `BoolOp` of the same-or-lower precedence.
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        self.push_str(op_str);
+                    }
+                    self.expr(value, next_precedence(prec))?;
+                }
+            }
+            BinOp { left, op, right } => {
+                // `**` is right-associative (`a ** b ** c` parses as
+                // `a ** (b ** c)`), the inverse of every other binary
+                // operator here; swap which side gets the strictly-higher
+                // precedence requirement so a left-nested `Pow` keeps its
+                // parens and a right-nested one doesn't need any.
+                let (left_prec, right_prec) = if matches!(op, ast::Operator::Pow) {
+                    (next_precedence(prec), prec)
+                } else {
+                    (prec, next_precedence(prec))
+                };
+                self.expr(left, left_prec)?;
+                write!(self.buffer, " {} ", operator_str(op)).unwrap();
+                self.expr(right, right_prec)?;
+            }
+            UnaryOp { op, operand } => {
+                self.push_str(unary_str(op));
+                self.expr(operand, Precedence::Unary)?;
+            }
+            Lambda { args, body } => {
+                self.push_str("lambda ");
+                self.arguments(args)?;
+                self.push_str(": ");
+                self.expr(body, Precedence::Tuple)?;
+            }
+            IfExp { test, body, orelse } => {
+                self.expr(body, Precedence::Or)?;
+                self.push_str(" if ");
+                self.expr(test, Precedence::Or)?;
+                self.push_str(" else ");
+                self.expr(orelse, Precedence::Ternary)?;
+            }
+            Dict { keys, values } => {
+                self.push('{');
+                for (i, (key, value)) in keys.iter().zip(values).enumerate() {
+                    if i > 0 {
+                        self.push_str(", ");
+                    }
+                    match key {
+                        Some(key) => {
+                            self.expr(key, Precedence::Tuple)?;
+                            self.push_str(": ");
+                            self.expr(value, Precedence::Tuple)?;
+                        }
+                        None => {
+                            self.push_str("**");
+                            self.expr(value, Precedence::Tuple)?;
+                        }
+                    }
+                }
+                self.push('}');
+            }
+            Set { elts } => {
+                self.push('{');
+                self.exprs(elts)?;
+                self.push('}');
+            }
+            ListComp { elt, generators } => self.comprehension('[', ']', elt, generators)?,
+            SetComp { elt, generators } => self.comprehension('{', '}', elt, generators)?,
+            DictComp {
+                key,
+                value,
+                generators,
+            } => {
+                self.push('{');
+                self.expr(key, Precedence::Tuple)?;
+                self.push_str(": ");
+                self.expr(value, Precedence::Tuple)?;
+                self.generators(generators)?;
+                self.push('}');
+            }
+            GeneratorExp { elt, generators } => self.comprehension('(', ')', elt, generators)?,
+            Await { value } => {
+                self.push_str("await ");
+                self.expr(value, Precedence::Atom)?;
+            }
+            Yield { value } => {
+                self.push_str("yield");
+                if let Some(value) = value {
+                    self.push(' ');
+                    self.expr(value, Precedence::Tuple)?;
+                }
+            }
+            YieldFrom { value } => {
+                self.push_str("yield from ");
+                self.expr(value, Precedence::Atom)?;
+            }
+            Compare {
+                left,
+                ops,
+                comparators,
+            } => {
+                // A bare chain like `a < b < c` is a single `Compare` node
+                // with two ops and needs no parens on either side. But an
+                // *explicit* nested `Compare` used as an operand — written
+                // source like `(a < b) < c` or `a < (b < c)` — must keep its
+                // parens, since CPython never re-nests a flat chain into a
+                // `Compare`-of-`Compare`. Using strictly-higher precedence
+                // for both operands forces parens exactly in that case.
+                self.expr(left, next_precedence(prec))?;
+                for (op, comparator) in ops.iter().zip(comparators) {
+                    write!(self.buffer, " {} ", cmpop_str(op)).unwrap();
+                    self.expr(comparator, next_precedence(prec))?;
+                }
+            }
+            Call {
+                func,
+                args,
+                keywords,
+            } => {
+                self.expr(func, Precedence::Atom)?;
+                self.push('(');
+                self.call_args(args, keywords)?;
+                self.push(')');
+            }
+            Constant { value, .. } => self.push_str(&constant_str(value)),
+            Attribute { value, attr, .. } => {
+                self.expr(value, Precedence::Atom)?;
+                write!(self.buffer, ".{}", attr).unwrap();
+            }
+            Subscript { value, slice, .. } => {
+                self.expr(value, Precedence::Atom)?;
+                self.push('[');
+                match &slice.node {
+                    // An index tuple is written bare (`x[a:b, c]`), since a
+                    // slice inside parentheses doesn't parse.
+                    Tuple { elts, .. } if !elts.is_empty() => {
+                        self.exprs(elts)?;
+                        if elts.len() == 1 {
+                            self.push(',');
+                        }
+                    }
+                    _ => self.expr(slice, Precedence::Tuple)?,
+                }
+                self.push(']');
+            }
+            Starred { value, .. } => {
+                self.push('*');
+                self.expr(value, Precedence::Atom)?;
+            }
+            Name { id, .. } => self.push_str(id),
+            List { elts, .. } => {
+                self.push('[');
+                self.exprs(elts)?;
+                self.push(']');
+            }
+            Tuple { elts, .. } => {
+                // Always bracketed, so a tuple inside a call, list, dict or
+                // another tuple keeps its grouping, and `()` stays valid.
+                self.push('(');
+                self.exprs(elts)?;
+                if elts.len() == 1 {
+                    self.push(',');
+                }
+                self.push(')');
+            }
+            Slice { lower, upper, step } => {
+                if let Some(lower) = lower {
+                    self.expr(lower, Precedence::Tuple)?;
+                }
+                self.push(':');
+                if let Some(upper) = upper {
+                    self.expr(upper, Precedence::Tuple)?;
+                }
+                if let Some(step) = step {
+                    self.push(':');
+                    self.expr(step, Precedence::Tuple)?;
+                }
+            }
+            other => {
+                return Err(self
+                    .vm
+                    .new_value_error(format!("don't know how to unparse {:?}", other)));
+            }
+        }
+        Ok(())
+    }
+
+    fn comprehension(
+        &mut self,
+        open: char,
+        close: char,
+        elt: &ast::Expr,
+        generators: &[ast::Comprehension],
+    ) -> PyResult<()> {
+        self.push(open);
+        self.expr(elt, Precedence::Tuple)?;
+        self.generators(generators)?;
+        self.push(close);
+        Ok(())
+    }
+
+    fn generators(&mut self, generators: &[ast::Comprehension]) -> PyResult<()> {
+        for generator in generators {
+            if generator.is_async {
+                self.push_str(" async");
+            }
+            self.push_str(" for ");
+            self.expr(&generator.target, Precedence::Tuple)?;
+            self.push_str(" in ");
+            self.expr(&generator.iter, Precedence::Or)?;
+            for if_clause in &generator.ifs {
+                self.push_str(" if ");
+                self.expr(if_clause, Precedence::Or)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn next_precedence(prec: Precedence) -> Precedence {
+    use Precedence::*;
+    match prec {
+        Tuple => Lambda,
+        Lambda => Ternary,
+        Ternary => Or,
+        Or => And,
+        And => Not,
+        Not => Compare,
+        Compare => BitOr,
+        BitOr => BitXor,
+        BitXor => BitAnd,
+        BitAnd => Shift,
+        Shift => Additive,
+        Additive => Multiplicative,
+        Multiplicative => Unary,
+        Unary => Power,
+        Power => Await,
+        Await => Atom,
+        Atom => Atom,
+    }
+}
+
+fn precedence(expr: &ast::ExprKind) -> Precedence {
+    use ast::ExprKind::*;
+    match expr {
+        Lambda { .. } => Precedence::Lambda,
+        IfExp { .. } => Precedence::Ternary,
+        BoolOp {
+            op: ast::Boolop::Or,
+            ..
+        } => Precedence::Or,
+        BoolOp {
+            op: ast::Boolop::And,
+            ..
+        } => Precedence::And,
+        UnaryOp {
+            op: ast::Unaryop::Not,
+            ..
+        } => Precedence::Not,
+        Compare { .. } => Precedence::Compare,
+        BinOp { op, .. } => match op {
+            ast::Operator::Add | ast::Operator::Sub => Precedence::Additive,
+            ast::Operator::Mult
+            | ast::Operator::Div
+            | ast::Operator::FloorDiv
+            | ast::Operator::Mod
+            | ast::Operator::MatMult => Precedence::Multiplicative,
+            ast::Operator::Pow => Precedence::Power,
+            ast::Operator::LShift | ast::Operator::RShift => Precedence::Shift,
+            ast::Operator::BitOr => Precedence::BitOr,
+            ast::Operator::BitXor => Precedence::BitXor,
+            ast::Operator::BitAnd => Precedence::BitAnd,
+        },
+        UnaryOp { .. } => Precedence::Unary,
+        Await { .. } => Precedence::Await,
+        // tuples bracket themselves (see `expr_inner`)
+        Tuple { .. } => Precedence::Atom,
+        _ => Precedence::Atom,
+    }
+}
+
+fn operator_str(op: &ast::Operator) -> &'static str {
+    match op {
+        ast::Operator::Add => "+",
+        ast::Operator::Sub => "-",
+        ast::Operator::Mult => "*",
+        ast::Operator::MatMult => "@",
+        ast::Operator::Div => "/",
+        ast::Operator::Mod => "%",
+        ast::Operator::Pow => "**",
+        ast::Operator::LShift => "<<",
+        ast::Operator::RShift => ">>",
+        ast::Operator::BitOr => "|",
+        ast::Operator::BitXor => "^",
+        ast::Operator::BitAnd => "&",
+        ast::Operator::FloorDiv => "//",
+    }
+}
+
+fn unary_str(op: &ast::Unaryop) -> &'static str {
+    match op {
+        ast::Unaryop::Invert => "~",
+        ast::Unaryop::Not => "not ",
+        ast::Unaryop::UAdd => "+",
+        ast::Unaryop::USub => "-",
+    }
+}
+
+fn cmpop_str(op: &ast::Cmpop) -> &'static str {
+    match op {
+        ast::Cmpop::Eq => "==",
+        ast::Cmpop::NotEq => "!=",
+        ast::Cmpop::Lt => "<",
+        ast::Cmpop::LtE => "<=",
+        ast::Cmpop::Gt => ">",
+        ast::Cmpop::GtE => ">=",
+        ast::Cmpop::Is => "is",
+        ast::Cmpop::IsNot => "is not",
+        ast::Cmpop::In => "in",
+        ast::Cmpop::NotIn => "not in",
+    }
+}
+
+fn constant_str(value: &ast::Constant) -> String {
+    match value {
+        ast::Constant::None => "None".to_owned(),
+        ast::Constant::Bool(b) => if *b { "True" } else { "False" }.to_owned(),
+        ast::Constant::Str(s) => repr_str(s),
+        ast::Constant::Bytes(b) => {
+            let mut out = String::from("b'");
+            for &byte in b {
+                match byte {
+                    b'\\' => out.push_str("\\\\"),
+                    b'\'' => out.push_str("\\'"),
+                    b'\n' => out.push_str("\\n"),
+                    b'\r' => out.push_str("\\r"),
+                    b'\t' => out.push_str("\\t"),
+                    0x20..=0x7e => out.push(byte as char),
+                    _ => write!(out, "\\x{:02x}", byte).unwrap(),
+                }
+            }
+            out.push('\'');
+            out
+        }
+        ast::Constant::Int(i) => i.to_string(),
+        ast::Constant::Tuple(elts) => {
+            let parts: Vec<String> = elts.iter().map(constant_str).collect();
+            if parts.len() == 1 {
+                format!("({},)", parts[0])
+            } else {
+                format!("({})", parts.join(", "))
+            }
+        }
+        ast::Constant::Float(f) => float_str(*f),
+        ast::Constant::Complex { real, imag } => {
+            // `inf`/`-inf`/`nan` aren't valid inside the bare `Nj`/`(a+bj)`
+            // complex literal syntax (e.g. `infj` lexes as a plain `Name`),
+            // so fall back to an explicit `complex(...)` call whenever
+            // either component isn't finite.
+            if !real.is_finite() || !imag.is_finite() {
+                format!("complex({}, {})", float_str(*real), float_str(*imag))
+            } else if *real == 0.0 {
+                format!("{}j", imag)
+            } else {
+                format!("({}+{}j)", real, imag)
+            }
+        }
+        ast::Constant::Ellipsis => "...".to_owned(),
+    }
+}
+
+/// Format a float as a literal that re-parses to the same value: the shortest
+/// round-tripping digits, always written out in full rather than in exponent
+/// form, so e.g. `1e300` doesn't match CPython's `repr()`. `inf`/`-inf`/`nan` are
+/// not valid Python float *literals* (a bare `inf` re-parses as a `Name`
+/// reference, not a `Constant`), so those are rendered as a `float(...)`
+/// call that evaluates back to the same value instead.
+fn float_str(f: f64) -> String {
+    if f.is_nan() {
+        "float('nan')".to_owned()
+    } else if f.is_infinite() {
+        if f > 0.0 {
+            "float('inf')".to_owned()
+        } else {
+            "float('-inf')".to_owned()
+        }
+    } else if f.fract() == 0.0 {
+        format!("{:.1}", f)
+    } else {
+        format!("{}", f)
+    }
+}
+
+/// Quote a string the way CPython's `repr()` would, preferring single quotes
+/// and falling back to double quotes when the value contains one.
+fn repr_str(s: &str) -> String {
+    let quote = if s.contains('\'') && !s.contains('"') {
+        '"'
+    } else {
+        '\''
+    };
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push(quote);
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c == quote => {
+                out.push('\\');
+                out.push(c);
+            }
+            c if c.is_control() => write!(out, "\\x{:02x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push(quote);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Interpreter;
+
+    fn constant_expr(value: ast::Constant) -> ast::Mod {
+        let location = ast::Location::new(1, 0);
+        ast::Mod::Expression {
+            body: Box::new(ast::Located {
+                location,
+                node: ast::ExprKind::Constant { value, kind: None },
+            }),
+        }
+    }
+
+    fn unparse_constant(value: ast::Constant) -> String {
+        Interpreter::without_stdlib(Default::default())
+            .enter(|vm| unparse(&constant_expr(value), vm).unwrap())
+    }
+
+    /// `inf`/`-inf`/`nan` aren't valid Python float literals on their own —
+    /// unparsing them as bare `Display` output would re-parse as references
+    /// to undefined names, not as the float they represent.
+    #[test]
+    fn non_finite_floats_unparse_as_float_calls() {
+        assert_eq!(
+            unparse_constant(ast::Constant::Float(f64::INFINITY)).trim(),
+            "float('inf')"
+        );
+        assert_eq!(
+            unparse_constant(ast::Constant::Float(f64::NEG_INFINITY)).trim(),
+            "float('-inf')"
+        );
+        assert_eq!(
+            unparse_constant(ast::Constant::Float(f64::NAN)).trim(),
+            "float('nan')"
+        );
+    }
+
+    #[test]
+    fn non_finite_complex_unparses_as_complex_call() {
+        assert_eq!(
+            unparse_constant(ast::Constant::Complex {
+                real: 0.0,
+                imag: f64::INFINITY,
+            })
+            .trim(),
+            "complex(0.0, float('inf'))"
+        );
+    }
+}